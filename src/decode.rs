@@ -0,0 +1,346 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::{Number, Value};
+
+use crate::parse::Format;
+
+// Well-known OIDs, see https://www.postgresql.org/docs/current/catalog-pg-type.html
+const BOOL: u32 = 16;
+const BYTEA: u32 = 17;
+const INT8: u32 = 20;
+const INT2: u32 = 21;
+const INT4: u32 = 23;
+const TEXT: u32 = 25;
+const JSON: u32 = 114;
+const FLOAT4: u32 = 700;
+const FLOAT8: u32 = 701;
+const BOOL_ARRAY: u32 = 1000;
+const INT2_ARRAY: u32 = 1005;
+const INT4_ARRAY: u32 = 1007;
+const TEXT_ARRAY: u32 = 1009;
+const INT8_ARRAY: u32 = 1016;
+const FLOAT4_ARRAY: u32 = 1021;
+const FLOAT8_ARRAY: u32 = 1022;
+const DATE: u32 = 1082;
+const TIMESTAMP: u32 = 1114;
+const TIMESTAMPTZ: u32 = 1184;
+const NUMERIC_ARRAY: u32 = 1231;
+const NUMERIC: u32 = 1700;
+const UUID: u32 = 2950;
+const UUID_ARRAY: u32 = 2951;
+const JSONB: u32 = 3802;
+
+/// Decode a column's value into the JSON representation matching its
+/// Postgres type (`Column::pg_type`) and wire `format`. Unknown OIDs fall
+/// back to a UTF-8 string (text format) or a base64 string (binary format).
+pub fn decode_value(oid: u32, raw: &[u8], format: Format) -> Value {
+    match format {
+        Format::Text => decode_text_value(oid, raw),
+        Format::Binary => decode_binary_value(oid, raw),
+    }
+}
+
+fn decode_text_value(oid: u32, raw: &[u8]) -> Value {
+    let text = String::from_utf8_lossy(raw);
+    match oid {
+        INT2 | INT4 | INT8 => text
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(text.into_owned())),
+        BOOL => Value::Bool(text == "t"),
+        FLOAT4 | FLOAT8 | NUMERIC => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(text.into_owned())),
+        JSON | JSONB => {
+            serde_json::from_str(&text).unwrap_or_else(|_| Value::String(text.into_owned()))
+        }
+        TIMESTAMP | TIMESTAMPTZ => timestamp_text_to_json(&text),
+        DATE => date_text_to_json(&text),
+        UUID => Value::String(text.into_owned()),
+        BYTEA => decode_bytea(&text)
+            .map(|bytes| Value::String(BASE64.encode(bytes)))
+            .unwrap_or_else(|| Value::String(text.into_owned())),
+        _ => match element_oid(oid) {
+            Some(element) => decode_array(element, &text),
+            None => Value::String(text.into_owned()),
+        },
+    }
+}
+
+// Decode a column sent in Postgres' fixed-width binary format. Types whose
+// binary layout isn't handled here (e.g. numeric) fall back to base64 of
+// the raw bytes, same as an unknown OID.
+fn decode_binary_value(oid: u32, raw: &[u8]) -> Value {
+    match (oid, raw.len()) {
+        (BOOL, 1) => Value::Bool(raw[0] != 0),
+        (INT2, 2) => Value::from(i16::from_be_bytes(raw.try_into().unwrap())),
+        (INT4, 4) => Value::from(i32::from_be_bytes(raw.try_into().unwrap())),
+        (INT8, 8) => Value::from(i64::from_be_bytes(raw.try_into().unwrap())),
+        (FLOAT4, 4) => Number::from_f64(f32::from_be_bytes(raw.try_into().unwrap()) as f64)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        (FLOAT8, 8) => Number::from_f64(f64::from_be_bytes(raw.try_into().unwrap()))
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        (TIMESTAMP, 8) | (TIMESTAMPTZ, 8) => {
+            timestamp_micros_to_json(i64::from_be_bytes(raw.try_into().unwrap()), raw)
+        }
+        (DATE, 4) => date_days_to_json(i32::from_be_bytes(raw.try_into().unwrap()), raw),
+        (UUID, 16) => Value::String(format_uuid(raw)),
+        (BYTEA, _) => Value::String(BASE64.encode(raw)),
+        // jsonb's binary format is a version byte followed by the same text
+        // as json's binary format (which is just the UTF-8 JSON itself).
+        (JSON, _) | (JSONB, _) => {
+            let text = if oid == JSONB { raw.get(1..).unwrap_or(b"") } else { raw };
+            serde_json::from_slice(text).unwrap_or_else(|_| Value::String(BASE64.encode(raw)))
+        }
+        _ => Value::String(BASE64.encode(raw)),
+    }
+}
+
+// Timestamp/date conversion needs `chrono` to turn the Postgres wire
+// representation into a calendar value; without it we fall back to the raw
+// text (or base64, for binary) the same way an unrecognized OID would.
+#[cfg(feature = "chrono")]
+fn timestamp_text_to_json(text: &str) -> Value {
+    decode_timestamp(text)
+        .map(|ts| Value::String(ts.to_rfc3339()))
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+#[cfg(not(feature = "chrono"))]
+fn timestamp_text_to_json(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+#[cfg(feature = "chrono")]
+fn date_text_to_json(text: &str) -> Value {
+    decode_date(text)
+        .map(|date| Value::String(date.format("%Y-%m-%d").to_string()))
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+#[cfg(not(feature = "chrono"))]
+fn date_text_to_json(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+#[cfg(feature = "chrono")]
+fn timestamp_micros_to_json(micros: i64, raw: &[u8]) -> Value {
+    datetime_from_micros(micros)
+        .map(|ts| Value::String(ts.to_rfc3339()))
+        .unwrap_or_else(|| Value::String(BASE64.encode(raw)))
+}
+
+#[cfg(not(feature = "chrono"))]
+fn timestamp_micros_to_json(_micros: i64, raw: &[u8]) -> Value {
+    Value::String(BASE64.encode(raw))
+}
+
+#[cfg(feature = "chrono")]
+fn date_days_to_json(days: i32, raw: &[u8]) -> Value {
+    date_from_days(days)
+        .map(|date| Value::String(date.format("%Y-%m-%d").to_string()))
+        .unwrap_or_else(|| Value::String(BASE64.encode(raw)))
+}
+
+#[cfg(not(feature = "chrono"))]
+fn date_days_to_json(_days: i32, raw: &[u8]) -> Value {
+    Value::String(BASE64.encode(raw))
+}
+
+#[cfg(feature = "chrono")]
+fn datetime_from_micros(micros: i64) -> Option<DateTime<Utc>> {
+    let epoch = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).single()?;
+    Some(epoch + Duration::microseconds(micros))
+}
+
+#[cfg(feature = "chrono")]
+fn date_from_days(days: i32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(2000, 1, 1)?.checked_add_signed(Duration::days(days as i64))
+}
+
+fn format_uuid(bytes: &[u8]) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-\
+         {:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[0],
+        bytes[1],
+        bytes[2],
+        bytes[3],
+        bytes[4],
+        bytes[5],
+        bytes[6],
+        bytes[7],
+        bytes[8],
+        bytes[9],
+        bytes[10],
+        bytes[11],
+        bytes[12],
+        bytes[13],
+        bytes[14],
+        bytes[15],
+    )
+}
+
+#[cfg(feature = "chrono")]
+fn decode_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z") {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|naive| DateTime::from_naive_utc_and_offset(naive, Utc))
+}
+
+#[cfg(feature = "chrono")]
+fn decode_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+}
+
+fn decode_bytea(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("\\x")?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Maps a Postgres array type OID to the OID of its element type, for the
+// handful of array types we know how to decode. Arrays of other types
+// still arrive as their raw `{...}` textual form via the fallback string.
+fn element_oid(array_oid: u32) -> Option<u32> {
+    Some(match array_oid {
+        BOOL_ARRAY => BOOL,
+        INT2_ARRAY => INT2,
+        INT4_ARRAY => INT4,
+        INT8_ARRAY => INT8,
+        TEXT_ARRAY => TEXT,
+        FLOAT4_ARRAY => FLOAT4,
+        FLOAT8_ARRAY => FLOAT8,
+        NUMERIC_ARRAY => NUMERIC,
+        UUID_ARRAY => UUID,
+        _ => return None,
+    })
+}
+
+// Parses the Postgres textual array syntax, e.g. `{1,2,NULL,4}` or
+// `{"a","b,c"}`, into a JSON array of decoded elements.
+fn decode_array(element_oid: u32, text: &str) -> Value {
+    let inner = text
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(text);
+    if inner.is_empty() {
+        return Value::Array(vec![]);
+    }
+
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if in_quotes => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ',' if !in_quotes => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+
+    Value::Array(
+        items
+            .into_iter()
+            .map(|item| {
+                if item == "NULL" {
+                    Value::Null
+                } else {
+                    decode_text_value(element_oid, item.as_bytes())
+                }
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_text_scalars() {
+        assert_eq!(decode_value(INT4, b"40", Format::Text), Value::from(40));
+        assert_eq!(decode_value(BOOL, b"t", Format::Text), Value::Bool(true));
+        assert_eq!(decode_value(BOOL, b"f", Format::Text), Value::Bool(false));
+        assert_eq!(
+            decode_value(TEXT, b"forty", Format::Text),
+            Value::String("forty".to_string())
+        );
+        assert_eq!(
+            decode_value(FLOAT8, b"1.5", Format::Text),
+            Value::from(1.5)
+        );
+    }
+
+    #[test]
+    fn decodes_binary_scalars() {
+        assert_eq!(
+            decode_value(INT4, &40i32.to_be_bytes(), Format::Binary),
+            Value::from(40)
+        );
+        assert_eq!(decode_value(BOOL, &[1], Format::Binary), Value::Bool(true));
+        assert_eq!(
+            decode_value(FLOAT8, &1.5f64.to_be_bytes(), Format::Binary),
+            Value::from(1.5)
+        );
+    }
+
+    #[test]
+    fn decodes_text_array() {
+        assert_eq!(
+            decode_value(INT4_ARRAY, b"{1,2,NULL,4}", Format::Text),
+            Value::Array(vec![
+                Value::from(1),
+                Value::from(2),
+                Value::Null,
+                Value::from(4),
+            ])
+        );
+    }
+
+    #[test]
+    fn decodes_json() {
+        assert_eq!(
+            decode_value(JSONB, br#"{"a":1}"#, Format::Text),
+            serde_json::json!({"a": 1})
+        );
+    }
+
+    #[test]
+    fn falls_back_to_base64_for_unhandled_binary() {
+        // NUMERIC's binary layout isn't decoded; it should fall back rather
+        // than panic or guess at the bytes.
+        let raw = [0u8, 1, 2, 3];
+        assert_eq!(
+            decode_value(NUMERIC, &raw, Format::Binary),
+            Value::String(BASE64.encode(raw))
+        );
+    }
+
+    #[test]
+    fn unknown_oid_falls_back_to_string() {
+        assert_eq!(
+            decode_value(999_999, b"whatever", Format::Text),
+            Value::String("whatever".to_string())
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+/// Errors produced while decoding a logical replication message.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown message type {0:?}")]
+    UnknownMessageType(char),
+    #[error("unknown tuple data flag {0:?}")]
+    UnknownTupleFlag(char),
+    /// The buffer ended before a complete message could be read. Unlike the
+    /// other variants this doesn't mean the stream is corrupt: a caller
+    /// reading from a socket should buffer more bytes and retry.
+    #[error("buffer ended before a complete message was read")]
+    UnexpectedEof,
+    #[error("invalid utf-8 in string field")]
+    InvalidUtf8(#[from] core::str::Utf8Error),
+    #[error("relation {0} has not been seen on this connection")]
+    UnknownRelation(u32),
+}
+
+impl Error {
+    /// True if this error means "wait for more bytes", as opposed to the
+    /// stream being corrupt.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, Error::UnexpectedEof)
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::decode::decode_value;
+use crate::error::Error;
+use crate::parse::{self, LogicalReplicationMessage, Relation, Tuple};
+
+/// Turns raw logical replication messages into JSON rows, keeping track of
+/// the `Relation` messages needed to resolve column names.
+pub struct Parser {
+    relations: HashMap<u32, Relation>,
+    current_lsn: Option<u64>,
+    // Whether we're between a `StreamStart` and its matching `StreamStop`,
+    // in which case row messages carry a leading xid.
+    in_stream: bool,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            relations: HashMap::new(),
+            current_lsn: None,
+            in_stream: false,
+        }
+    }
+
+    /// Decode a single logical replication message. Row changes (insert,
+    /// update, delete) are returned as JSON; everything else only updates
+    /// internal state and returns `None`.
+    pub fn to_json(&mut self, src: &[u8]) -> Result<Option<Value>, Error> {
+        let message = if self.in_stream {
+            parse::parse_streaming(src)?
+        } else {
+            parse::parse(src)?
+        };
+        match message {
+            LogicalReplicationMessage::Begin(begin) => {
+                self.current_lsn = Some(begin.lsn);
+                Ok(None)
+            }
+            LogicalReplicationMessage::StreamStart(_) => {
+                self.in_stream = true;
+                Ok(None)
+            }
+            LogicalReplicationMessage::StreamStop(_) => {
+                self.in_stream = false;
+                Ok(None)
+            }
+            LogicalReplicationMessage::StreamCommit(commit) => {
+                self.current_lsn = Some(commit.lsn);
+                Ok(None)
+            }
+            LogicalReplicationMessage::Relation(relation) => {
+                self.relations.insert(relation.id, relation);
+                Ok(None)
+            }
+            LogicalReplicationMessage::Insert(insert) => {
+                let relation = self.relation(insert.relation_id)?;
+                Ok(Some(json!({
+                    "relation": relation_name(relation),
+                    "action": "insert",
+                    "lsn": self.current_lsn,
+                    "data": row_to_json(relation, &insert.row),
+                })))
+            }
+            LogicalReplicationMessage::Update(update) => {
+                let relation = self.relation(update.relation_id)?;
+                let mut value = json!({
+                    "relation": relation_name(relation),
+                    "action": "update",
+                    "lsn": self.current_lsn,
+                    "data": row_to_json(relation, &update.row),
+                });
+                if let Some(old_row) = &update.old_row {
+                    // `old` (REPLICA IDENTITY FULL) carries the full
+                    // previous row; `key` only carries the replica identity
+                    // columns. Omitted entirely when neither was sent.
+                    let label = if update.old { "old" } else { "key" };
+                    value[label] = row_to_json(relation, old_row);
+                }
+                Ok(Some(value))
+            }
+            LogicalReplicationMessage::Delete(delete) => {
+                let relation = self.relation(delete.relation_id)?;
+                Ok(Some(json!({
+                    "relation": relation_name(relation),
+                    "action": "delete",
+                    "lsn": self.current_lsn,
+                    "old": row_to_json(relation, &delete.row),
+                })))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn relation(&self, id: u32) -> Result<&Relation, Error> {
+        self.relations.get(&id).ok_or(Error::UnknownRelation(id))
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn relation_name(relation: &Relation) -> String {
+    format!("{}.{}", relation.namespace, relation.name)
+}
+
+fn row_to_json(relation: &Relation, row: &[Tuple]) -> Value {
+    let mut data = serde_json::Map::with_capacity(row.len());
+    for (column, tuple) in relation.columns.iter().zip(row.iter()) {
+        match tuple.format() {
+            None => {
+                // Explicit NULL ('n'); unchanged TOASTed values ('u') are
+                // omitted entirely rather than guessing at their content.
+                if tuple.flag == 'n' {
+                    data.insert(column.name.clone(), Value::Null);
+                }
+            }
+            Some(format) => {
+                let raw = tuple.value.as_deref().unwrap_or_default();
+                data.insert(column.name.clone(), decode_value(column.pg_type, raw, format));
+            }
+        }
+    }
+    Value::Object(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cstr(out: &mut Vec<u8>, s: &str) {
+        out.extend_from_slice(s.as_bytes());
+        out.push(0);
+    }
+
+    fn relation_msg(id: u32, name: &str, columns: &[(bool, &str, u32)]) -> Vec<u8> {
+        let mut out = vec![b'R'];
+        out.extend_from_slice(&id.to_be_bytes());
+        cstr(&mut out, "public");
+        cstr(&mut out, name);
+        out.push(b'd');
+        out.extend_from_slice(&(columns.len() as u16).to_be_bytes());
+        for (key, name, pg_type) in columns {
+            out.push(if *key { 1 } else { 0 });
+            cstr(&mut out, name);
+            out.extend_from_slice(&pg_type.to_be_bytes());
+            out.extend_from_slice(&(-1i32).to_be_bytes());
+        }
+        out
+    }
+
+    fn push_tupledata(out: &mut Vec<u8>, values: &[Option<&str>]) {
+        out.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        for value in values {
+            match value {
+                None => out.push(b'n'),
+                Some(v) => {
+                    out.push(b't');
+                    out.extend_from_slice(&(v.len() as u32).to_be_bytes());
+                    out.extend_from_slice(v.as_bytes());
+                }
+            }
+        }
+    }
+
+    fn insert_msg(relation_id: u32, values: &[Option<&str>]) -> Vec<u8> {
+        let mut out = vec![b'I'];
+        out.extend_from_slice(&relation_id.to_be_bytes());
+        out.push(b'N');
+        push_tupledata(&mut out, values);
+        out
+    }
+
+    fn update_msg(
+        relation_id: u32,
+        old: Option<(bool, &[Option<&str>])>,
+        new: &[Option<&str>],
+    ) -> Vec<u8> {
+        let mut out = vec![b'U'];
+        out.extend_from_slice(&relation_id.to_be_bytes());
+        if let Some((full, old_values)) = old {
+            out.push(if full { b'O' } else { b'K' });
+            push_tupledata(&mut out, old_values);
+        }
+        out.push(b'N');
+        push_tupledata(&mut out, new);
+        out
+    }
+
+    fn delete_msg(relation_id: u32, key: bool, old: &[Option<&str>]) -> Vec<u8> {
+        let mut out = vec![b'D'];
+        out.extend_from_slice(&relation_id.to_be_bytes());
+        out.push(if key { b'K' } else { b'O' });
+        push_tupledata(&mut out, old);
+        out
+    }
+
+    const COLUMNS: &[(bool, &str, u32)] = &[(true, "id", 23), (false, "val", 25)];
+
+    #[test]
+    fn insert_produces_row_json() {
+        let mut parser = Parser::new();
+        parser.to_json(&relation_msg(1, "widgets", COLUMNS)).unwrap();
+        let value = parser
+            .to_json(&insert_msg(1, &[Some("40"), Some("forty")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "relation": "public.widgets",
+                "action": "insert",
+                "lsn": null,
+                "data": {"id": 40, "val": "forty"},
+            })
+        );
+    }
+
+    #[test]
+    fn update_without_old_data_omits_key_and_old_fields() {
+        let mut parser = Parser::new();
+        parser.to_json(&relation_msg(1, "widgets", COLUMNS)).unwrap();
+        let value = parser
+            .to_json(&update_msg(1, None, &[Some("40"), Some("fifty")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "relation": "public.widgets",
+                "action": "update",
+                "lsn": null,
+                "data": {"id": 40, "val": "fifty"},
+            })
+        );
+    }
+
+    #[test]
+    fn update_with_replica_identity_key_includes_key_field() {
+        let mut parser = Parser::new();
+        parser.to_json(&relation_msg(1, "widgets", COLUMNS)).unwrap();
+        let value = parser
+            .to_json(&update_msg(
+                1,
+                Some((false, &[Some("40"), None])),
+                &[Some("40"), Some("fifty")],
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(value["key"], json!({"id": 40, "val": null}));
+        assert_eq!(value["data"], json!({"id": 40, "val": "fifty"}));
+    }
+
+    #[test]
+    fn update_with_replica_identity_full_includes_old_field() {
+        let mut parser = Parser::new();
+        parser.to_json(&relation_msg(1, "widgets", COLUMNS)).unwrap();
+        let value = parser
+            .to_json(&update_msg(
+                1,
+                Some((true, &[Some("40"), Some("forty")])),
+                &[Some("40"), Some("fifty")],
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(value["old"], json!({"id": 40, "val": "forty"}));
+        assert_eq!(value["data"], json!({"id": 40, "val": "fifty"}));
+    }
+
+    #[test]
+    fn delete_emits_old_row() {
+        let mut parser = Parser::new();
+        parser.to_json(&relation_msg(1, "widgets", COLUMNS)).unwrap();
+        let value = parser
+            .to_json(&delete_msg(1, true, &[Some("40"), None]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "relation": "public.widgets",
+                "action": "delete",
+                "lsn": null,
+                "old": {"id": 40, "val": null},
+            })
+        );
+    }
+
+    #[test]
+    fn insert_for_unknown_relation_errors() {
+        let mut parser = Parser::new();
+        let err = parser.to_json(&insert_msg(1, &[Some("40")])).unwrap_err();
+        assert!(matches!(err, Error::UnknownRelation(1)));
+    }
+}
@@ -0,0 +1,19 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod error;
+pub mod parse;
+pub mod time;
+
+#[cfg(feature = "json")]
+pub mod decode;
+#[cfg(feature = "json")]
+pub mod json;
+
+#[cfg(feature = "json")]
+pub use decode::decode_value;
+pub use error::Error;
+#[cfg(feature = "json")]
+pub use json::Parser;
+pub use parse::*;
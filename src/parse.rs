@@ -1,95 +1,116 @@
-use std::io::BufRead;
-use std::ops::Add;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 use bytes::Buf;
-use chrono::prelude::*;
-use chrono::Utc;
-use chrono::{DateTime, Duration};
+
+use crate::error::Error;
+use crate::time::Timestamp;
 
 pub trait Decoder: Buf {
-    fn get_bool(&mut self) -> bool;
-    fn get_string(&mut self) -> String
+    fn get_bool(&mut self) -> Result<bool, Error>;
+    fn get_string(&mut self) -> Result<String, Error>
     where
         Self: Sized;
-    fn get_timestamp(&mut self) -> DateTime<Utc>;
-    fn get_rowinfo(&mut self, byte: char) -> bool;
-    fn get_tupledata(&mut self) -> Vec<Tuple>;
-    fn get_columns(&mut self) -> Vec<Column>
+    fn get_timestamp(&mut self) -> Result<Timestamp, Error>;
+    fn get_rowinfo(&mut self, byte: char) -> Result<bool, Error>;
+    fn get_tupledata(&mut self) -> Result<Vec<Tuple>, Error>;
+    fn get_columns(&mut self) -> Result<Vec<Column>, Error>
     where
         Self: Sized;
 }
 
+// Returns `Error::UnexpectedEof` instead of letting `bytes::Buf` panic when
+// fewer than `n` bytes remain.
+fn require(buf: &[u8], n: usize) -> Result<(), Error> {
+    if buf.remaining() < n {
+        Err(Error::UnexpectedEof)
+    } else {
+        Ok(())
+    }
+}
+
 impl Decoder for &[u8] {
-    fn get_bool(&mut self) -> bool {
-        self.get_u8() != 0
+    fn get_bool(&mut self) -> Result<bool, Error> {
+        require(self, 1)?;
+        Ok(self.get_u8() != 0)
     }
 
-    fn get_string(&mut self) -> String
+    fn get_string(&mut self) -> Result<String, Error>
     where
         Self: Sized,
     {
-        let mut buf = vec![];
-        self.reader().read_until(0, &mut buf).unwrap();
-        buf.pop();
-        std::str::from_utf8(&buf).unwrap().to_string()
+        let end = self
+            .chunk()
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::UnexpectedEof)?;
+        let bytes = self.chunk()[..end].to_vec();
+        self.advance(end + 1);
+        Ok(core::str::from_utf8(&bytes)?.to_string())
     }
 
-    fn get_timestamp(&mut self) -> DateTime<Utc> {
-        let micro = self.get_u64();
-        let ts = Utc.ymd(2000, 1, 1).and_hms(0, 0, 0);
-        ts.add(Duration::from_std(std::time::Duration::from_micros(micro)).unwrap())
+    fn get_timestamp(&mut self) -> Result<Timestamp, Error> {
+        require(self, 8)?;
+        Ok(self.get_u64() as Timestamp)
     }
 
-    fn get_rowinfo(&mut self, byte: char) -> bool {
-        assert!(self.remaining() >= 1);
-        match self.chunk()[0] as char == byte {
-            true => {
-                self.advance(1);
-                true
-            }
-            false => false,
+    fn get_rowinfo(&mut self, byte: char) -> Result<bool, Error> {
+        require(self, 1)?;
+        if self.chunk()[0] as char == byte {
+            self.advance(1);
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
-    fn get_tupledata(&mut self) -> Vec<Tuple> {
+    fn get_tupledata(&mut self) -> Result<Vec<Tuple>, Error> {
+        require(self, 2)?;
         let size = self.get_u16();
         let mut data = Vec::<Tuple>::with_capacity(size as usize);
         for _ in 0..size {
+            require(self, 1)?;
             let flag = self.get_u8() as char;
             match flag {
-                'n' | 'u' => data.push(Tuple {
-                    flag: flag,
-                    value: None,
-                }),
-                't' => {
+                'n' | 'u' => data.push(Tuple { flag, value: None }),
+                // 't' (text) and 'b' (binary) share the same `Int32 length,
+                // Byte[length] value` layout; only the encoding of `value`
+                // differs, which `Tuple::format` exposes to callers.
+                't' | 'b' => {
+                    require(self, 4)?;
                     let vsize = self.get_u32() as usize;
+                    require(self, vsize)?;
                     data.push(Tuple {
-                        flag: flag as char,
-                        value: Some((&self.chunk()[..vsize]).to_vec()),
+                        flag,
+                        value: Some(self.chunk()[..vsize].to_vec()),
                     });
                     self.advance(vsize);
                 }
-                _ => panic!("Unknown data type flag: {:?}", flag),
+                _ => return Err(Error::UnknownTupleFlag(flag)),
             }
         }
-        data
+        Ok(data)
     }
 
-    fn get_columns(&mut self) -> Vec<Column>
+    fn get_columns(&mut self) -> Result<Vec<Column>, Error>
     where
         Self: Sized,
     {
+        require(self, 2)?;
         let size = self.get_u16();
         let mut data = Vec::<Column>::with_capacity(size as usize);
         for _ in 0..size {
+            let key = self.get_bool()?;
+            let name = self.get_string()?;
+            require(self, 8)?;
             data.push(Column {
-                key: self.get_bool(),
-                name: self.get_string(),
+                key,
+                name,
                 pg_type: self.get_u32(),
                 mode: self.get_u32(),
             });
         }
-        data
+        Ok(data)
     }
 }
 
@@ -98,7 +119,7 @@ pub struct Begin {
     pub lsn: u64,
     // Commit timestamp of the transaction. The value is in number of
     // microseconds since PostgreSQL epoch (2000-01-01).
-    pub timestamp: DateTime<Utc>,
+    pub timestamp: Timestamp,
     // Xid of the transaction.
     pub xid: i32,
 }
@@ -109,10 +130,12 @@ pub struct Commit {
     pub lsn: u64,
     // The final LSN of the transaction.
     pub transaction_lsn: u64,
-    pub timestamp: DateTime<Utc>,
+    pub timestamp: Timestamp,
 }
 
 pub struct Relation {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
     // ID of the relation.
     pub id: u32,
     // Namespace (empty string for pg_catalog).
@@ -129,6 +152,8 @@ impl Relation {
 }
 
 pub struct Type {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
     // ID of the data type
     pub id: u32,
     pub namespace: String,
@@ -136,6 +161,8 @@ pub struct Type {
 }
 
 pub struct Insert {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
     /// ID of the relation corresponding to the ID in the relation message.
     pub relation_id: u32,
     // Identifies the following TupleData message as a new tuple.
@@ -144,6 +171,8 @@ pub struct Insert {
 }
 
 pub struct Update {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
     /// ID of the relation corresponding to the ID in the relation message.
     pub relation_id: u32,
     // Identifies the following TupleData message as a new tuple.
@@ -155,6 +184,8 @@ pub struct Update {
 }
 
 pub struct Delete {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
     /// ID of the relation corresponding to the ID in the relation message.
     pub relation_id: u32,
     // Identifies the following TupleData message as a new tuple.
@@ -168,11 +199,46 @@ pub struct Origin {
     pub name: String,
 }
 
-// TODO: Add support for more Postgres types
-// pub DecoderValue interface {
-// 	pgtype.TextDecoder
-// 	pgtype.Value
-// }
+pub struct Truncate {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
+    pub cascade: bool,
+    pub restart_identity: bool,
+    pub relation_ids: Vec<u32>,
+}
+
+pub struct Message {
+    // Xid of the transaction, present when streaming an in-progress transaction.
+    pub xid: Option<u32>,
+    // Whether the message is transactional (bit 1 of flags).
+    pub transactional: bool,
+    pub lsn: u64,
+    pub prefix: String,
+    pub content: Vec<u8>,
+}
+
+pub struct StreamStart {
+    pub xid: u32,
+    // True if this is the first stream segment for this transaction.
+    pub first_segment: bool,
+}
+
+pub struct StreamStop {}
+
+pub struct StreamCommit {
+    pub xid: u32,
+    pub flags: u8,
+    // The final LSN of the transaction.
+    pub lsn: u64,
+    // The final LSN of the transaction.
+    pub transaction_lsn: u64,
+    pub timestamp: Timestamp,
+}
+
+pub struct StreamAbort {
+    pub xid: u32,
+    pub sub_xid: u32,
+}
 
 pub struct Column {
     pub key: bool,
@@ -186,6 +252,26 @@ pub struct Tuple {
     pub value: Option<Vec<u8>>,
 }
 
+/// Wire encoding of a `Tuple`'s value, negotiated per-column by the
+/// publication/slot's `binary` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+impl Tuple {
+    /// The encoding `value` is in, or `None` for a null/unchanged-toast
+    /// tuple that carries no value at all.
+    pub fn format(&self) -> Option<Format> {
+        match self.flag {
+            't' => Some(Format::Text),
+            'b' => Some(Format::Binary),
+            _ => None,
+        }
+    }
+}
+
 pub enum LogicalReplicationMessage {
     Begin(Begin),
     Commit(Commit),
@@ -195,72 +281,244 @@ pub enum LogicalReplicationMessage {
     Insert(Insert),
     Update(Update),
     Delete(Delete),
+    Truncate(Truncate),
+    Message(Message),
+    StreamStart(StreamStart),
+    StreamStop(StreamStop),
+    StreamCommit(StreamCommit),
+    StreamAbort(StreamAbort),
 }
 
 // Parse a logical replication message.
 // See https://www.postgresql.org/docs/current/static/protocol-logicalrep-message-formats.html
-pub fn parse(src: &[u8]) -> Result<LogicalReplicationMessage, String> {
+pub fn parse(src: &[u8]) -> Result<LogicalReplicationMessage, Error> {
+    parse_message(src, false)
+}
+
+// Parse a logical replication message that may be part of an in-progress
+// (streamed) transaction, i.e. `streaming = on` was negotiated for the
+// replication slot. In that mode `R`/`Y`/`I`/`U`/`D`/`T` messages are each
+// preceded by the `Int32` xid of the (sub-)transaction they belong to, so
+// that rows can be associated with their transaction ahead of the matching
+// `StreamCommit`/`StreamAbort`.
+pub fn parse_streaming(src: &[u8]) -> Result<LogicalReplicationMessage, Error> {
+    parse_message(src, true)
+}
+
+fn read_xid(buf: &mut &[u8], streaming: bool) -> Result<Option<u32>, Error> {
+    if streaming {
+        require(buf, 4)?;
+        Ok(Some(buf.get_u32()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_message(src: &[u8], streaming: bool) -> Result<LogicalReplicationMessage, Error> {
+    require(src, 1)?;
     let msg_type = src[0] as char;
     let mut buf = &src[1..];
     match msg_type {
-        'B' => Ok(LogicalReplicationMessage::Begin(Begin {
-            lsn: buf.get_u64(),
-            timestamp: buf.get_timestamp(),
-            xid: buf.get_i32(),
-        })),
-        'C' => Ok(LogicalReplicationMessage::Commit(Commit {
-            flags: buf.get_u8(),
-            lsn: buf.get_u64(),
-            transaction_lsn: buf.get_u64(),
-            timestamp: buf.get_timestamp(),
-        })),
-        'O' => Ok(LogicalReplicationMessage::Origin(Origin {
-            lsn: buf.get_u64(),
-            name: buf.get_string(),
-        })),
-        'R' => Ok(LogicalReplicationMessage::Relation(Relation {
-            id: buf.get_u32(),
-            namespace: buf.get_string(),
-            name: buf.get_string(),
-            replica: buf.get_u8(),
-            columns: buf.get_columns(),
-        })),
-        'Y' => Ok(LogicalReplicationMessage::Type(Type {
-            id: buf.get_u32(),
-            namespace: buf.get_string(),
-            name: buf.get_string(),
-        })),
-        'I' => Ok(LogicalReplicationMessage::Insert(Insert {
-            relation_id: buf.get_u32(),
-            new: buf.get_bool(),
-            row: buf.get_tupledata(),
-        })),
+        'B' => {
+            require(buf, 8)?;
+            let lsn = buf.get_u64();
+            let timestamp = buf.get_timestamp()?;
+            require(buf, 4)?;
+            let xid = buf.get_i32();
+            Ok(LogicalReplicationMessage::Begin(Begin {
+                lsn,
+                timestamp,
+                xid,
+            }))
+        }
+        'C' => {
+            require(buf, 1 + 8 + 8)?;
+            let flags = buf.get_u8();
+            let lsn = buf.get_u64();
+            let transaction_lsn = buf.get_u64();
+            let timestamp = buf.get_timestamp()?;
+            Ok(LogicalReplicationMessage::Commit(Commit {
+                flags,
+                lsn,
+                transaction_lsn,
+                timestamp,
+            }))
+        }
+        'O' => {
+            require(buf, 8)?;
+            let lsn = buf.get_u64();
+            let name = buf.get_string()?;
+            Ok(LogicalReplicationMessage::Origin(Origin { lsn, name }))
+        }
+        'R' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 4)?;
+            let id = buf.get_u32();
+            let namespace = buf.get_string()?;
+            let name = buf.get_string()?;
+            require(buf, 1)?;
+            let replica = buf.get_u8();
+            let columns = buf.get_columns()?;
+            Ok(LogicalReplicationMessage::Relation(Relation {
+                xid,
+                id,
+                namespace,
+                name,
+                replica,
+                columns,
+            }))
+        }
+        'Y' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 4)?;
+            let id = buf.get_u32();
+            let namespace = buf.get_string()?;
+            let name = buf.get_string()?;
+            Ok(LogicalReplicationMessage::Type(Type {
+                xid,
+                id,
+                namespace,
+                name,
+            }))
+        }
+        'I' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 4)?;
+            let relation_id = buf.get_u32();
+            let new = buf.get_bool()?;
+            let row = buf.get_tupledata()?;
+            Ok(LogicalReplicationMessage::Insert(Insert {
+                xid,
+                relation_id,
+                new,
+                row,
+            }))
+        }
         'U' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 4)?;
             let relation_id = buf.get_u32();
-            let key = buf.get_rowinfo('K');
-            let old = buf.get_rowinfo('O');
-            let old_row: Option<Vec<Tuple>> = None;
-            if key || old {
-                let _old_row = buf.get_tupledata();
-            }
-            let new = buf.get_bool();
-            let row = buf.get_tupledata();
+            let key = buf.get_rowinfo('K')?;
+            let old = buf.get_rowinfo('O')?;
+            let old_row = if key || old {
+                Some(buf.get_tupledata()?)
+            } else {
+                None
+            };
+            let new = buf.get_bool()?;
+            let row = buf.get_tupledata()?;
 
             Ok(LogicalReplicationMessage::Update(Update {
-                relation_id: relation_id,
-                key: key,
-                old: old,
-                old_row: old_row,
-                new: new,
-                row: row,
+                xid,
+                relation_id,
+                key,
+                old,
+                old_row,
+                new,
+                row,
+            }))
+        }
+        'D' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 4)?;
+            let relation_id = buf.get_u32();
+            let key = buf.get_rowinfo('K')?;
+            let old = buf.get_rowinfo('O')?;
+            let row = buf.get_tupledata()?;
+            Ok(LogicalReplicationMessage::Delete(Delete {
+                xid,
+                relation_id,
+                key,
+                old,
+                row,
             }))
         }
-        'D' => Ok(LogicalReplicationMessage::Delete(Delete {
-            relation_id: buf.get_u32(),
-            key: buf.get_rowinfo('K'),
-            old: buf.get_rowinfo('O'),
-            row: buf.get_tupledata(),
-        })),
-        _ => Err(format!("Unknown message type {}", msg_type)),
+        'T' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 5)?;
+            let num_relations = buf.get_u32();
+            let flags = buf.get_u8();
+            // Bounds-check each id individually rather than the whole block
+            // up front: `num_relations` is attacker-controlled, so neither
+            // `num_relations as usize * 4` (can overflow `usize` on 32-bit
+            // targets) nor `Vec::with_capacity(num_relations as usize)` (can
+            // attempt a huge allocation before the buffer is even read) are
+            // safe to do with the raw count.
+            let mut relation_ids = Vec::new();
+            for _ in 0..num_relations {
+                require(buf, 4)?;
+                relation_ids.push(buf.get_u32());
+            }
+            Ok(LogicalReplicationMessage::Truncate(Truncate {
+                xid,
+                cascade: flags & 0b1 != 0,
+                restart_identity: flags & 0b10 != 0,
+                relation_ids,
+            }))
+        }
+        'M' => {
+            let xid = read_xid(&mut buf, streaming)?;
+            require(buf, 9)?;
+            let flags = buf.get_u8();
+            let lsn = buf.get_u64();
+            let prefix = buf.get_string()?;
+            require(buf, 4)?;
+            let length = buf.get_u32() as usize;
+            require(buf, length)?;
+            let content = buf.chunk()[..length].to_vec();
+            buf.advance(length);
+            Ok(LogicalReplicationMessage::Message(Message {
+                xid,
+                transactional: flags & 0b1 != 0,
+                lsn,
+                prefix,
+                content,
+            }))
+        }
+        'S' => {
+            require(buf, 5)?;
+            Ok(LogicalReplicationMessage::StreamStart(StreamStart {
+                xid: buf.get_u32(),
+                first_segment: buf.get_u8() == 1,
+            }))
+        }
+        'E' => Ok(LogicalReplicationMessage::StreamStop(StreamStop {})),
+        'c' => {
+            require(buf, 29)?;
+            Ok(LogicalReplicationMessage::StreamCommit(StreamCommit {
+                xid: buf.get_u32(),
+                flags: buf.get_u8(),
+                lsn: buf.get_u64(),
+                transaction_lsn: buf.get_u64(),
+                timestamp: buf.get_timestamp()?,
+            }))
+        }
+        'A' => {
+            require(buf, 8)?;
+            Ok(LogicalReplicationMessage::StreamAbort(StreamAbort {
+                xid: buf.get_u32(),
+                sub_xid: buf.get_u32(),
+            }))
+        }
+        _ => Err(Error::UnknownMessageType(msg_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_with_relation_count_past_buffer_errors_instead_of_panicking() {
+        // `num_relations` claims far more relation ids than the buffer
+        // actually holds (and, on a 32-bit `usize`, is close enough to
+        // `2^30` that a naive `num_relations as usize * 4` byte-count would
+        // wrap around). This must report `UnexpectedEof`, not panic.
+        let mut msg = vec![b'T'];
+        msg.extend_from_slice(&0x4000_0000u32.to_be_bytes());
+        msg.push(0); // flags
+        msg.extend_from_slice(&1u32.to_be_bytes()); // one relation id present
+
+        assert!(matches!(parse(&msg), Err(Error::UnexpectedEof)));
     }
 }
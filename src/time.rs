@@ -0,0 +1,19 @@
+//! Timestamp handling decoupled from any particular clock/calendar crate, so
+//! the parsing core has no `std::time`/OS dependency and can build for
+//! targets like `wasm32-unknown-unknown`.
+
+/// Microseconds since the Postgres epoch (2000-01-01 00:00:00 UTC), exactly
+/// as it appears on the wire. Enable the `chrono` feature for a `DateTime`
+/// conversion.
+pub type Timestamp = i64;
+
+/// Convert a raw [`Timestamp`] into a `chrono::DateTime<Utc>`.
+#[cfg(feature = "chrono")]
+pub fn to_datetime(timestamp: Timestamp) -> chrono::DateTime<chrono::Utc> {
+    use chrono::{Duration, TimeZone, Utc};
+    let epoch = Utc
+        .with_ymd_and_hms(2000, 1, 1, 0, 0, 0)
+        .single()
+        .expect("2000-01-01 00:00:00 is a valid UTC datetime");
+    epoch + Duration::microseconds(timestamp)
+}
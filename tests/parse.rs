@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use glob::glob;
 
-use pgoutput2json;
+use pgoutput2json::{self, decode_value, LogicalReplicationMessage, Relation};
 
 #[test]
 fn parse_wal_data() {
@@ -8,7 +10,7 @@ fn parse_wal_data() {
         id: u32,
         val: String,
     }
-    let _expected: std::collections::HashMap<u32, ExpectedRow> = std::collections::HashMap::from([
+    let expected: HashMap<u32, ExpectedRow> = HashMap::from([
         (
             2,
             ExpectedRow {
@@ -31,28 +33,53 @@ fn parse_wal_data() {
             },
         ),
     ]);
-    for filepath in glob("testdata/*.waldata").unwrap() {
-        println!("filepath: {:?}", filepath);
-        let waldata = std::fs::read(filepath.unwrap()).unwrap();
-        let m = pgoutput2json::parse(&waldata).unwrap();
-
-        match m {
-            pgoutput2json::LogicalReplicationMessage::Relation(relation) => {
-                println!("id: {:?}", relation.id);
-                println!("namespace: {:?}", relation.namespace);
-                println!("name: {:?}", relation.name);
-                println!("replica: {:?}", relation.replica);
+
+    let mut relations: HashMap<u32, Relation> = HashMap::new();
+    let mut seen_relations = 0;
+    let mut seen_inserts = 0;
+
+    let mut filepaths: Vec<_> = glob("testdata/*.waldata").unwrap().map(|p| p.unwrap()).collect();
+    filepaths.sort();
+    assert!(!filepaths.is_empty(), "no testdata/*.waldata fixtures found");
+
+    for filepath in filepaths {
+        let waldata = std::fs::read(&filepath).unwrap();
+        let message = pgoutput2json::parse(&waldata).unwrap();
+
+        match message {
+            LogicalReplicationMessage::Relation(relation) => {
+                seen_relations += 1;
+                relations.insert(relation.id, relation);
             }
-            pgoutput2json::LogicalReplicationMessage::Insert(insert) => {
-                println!("relation_id: {:?}", insert.relation_id);
-                println!("new: {:?}", insert.new);
+            LogicalReplicationMessage::Insert(insert) => {
+                seen_inserts += 1;
+                let relation = relations.get(&insert.relation_id).unwrap();
+                let want = expected.get(&insert.relation_id).unwrap();
+
+                let mut got_id = None;
+                let mut got_val = None;
+                for (column, tuple) in relation.columns.iter().zip(insert.row.iter()) {
+                    let format = tuple.format().unwrap();
+                    let raw = tuple.value.as_deref().unwrap_or_default();
+                    let value = decode_value(column.pg_type, raw, format);
+                    match column.name.as_str() {
+                        "id" => got_id = value.as_i64(),
+                        "val" => got_val = value.as_str().map(|s| s.to_string()),
+                        other => panic!("unexpected column {other:?}"),
+                    }
+                }
+                assert_eq!(got_id, Some(want.id as i64));
+                assert_eq!(got_val.as_deref(), Some(want.val.as_str()));
             }
-            pgoutput2json::LogicalReplicationMessage::Type(type_) => {
+            LogicalReplicationMessage::Type(type_) => {
                 assert_eq!(type_.id, 35756);
                 assert_eq!(type_.namespace, "public");
                 assert_eq!(type_.name, "ticket_state");
             }
-            _ => {}
+            _ => panic!("unexpected message type in fixtures"),
         }
     }
+
+    assert_eq!(seen_relations, 3);
+    assert_eq!(seen_inserts, 3);
 }